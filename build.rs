@@ -4,11 +4,13 @@ use std::env;
 use std::iter::FromIterator;
 use std::collections::HashSet;
 use std::convert::AsRef;
+use std::io::Read;
 use std::path::{PathBuf, Path};
 use std::string::ToString;
 use std::process::Command;
 use tar::Archive;
 use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
 
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -42,35 +44,54 @@ fn has_env_var_with_value(s: &str, v: &str) -> bool {
 // PATHS
 ///////////////////////////////////////////////////////////////////////////////
 
-pub const STATIC_LIBS: &[(&str, &str)] = &[
-    (
-        "avcodec",
-        "libavcodec/libavcodec.a",
-    ),
-    (
-        "avdevice",
-        "libavdevice/libavdevice.a",
-    ),
-    (
-        "avfilter",
-        "libavfilter/libavfilter.a",
-    ),
-    (
-        "avformat",
-        "libavformat/libavformat.a",
-    ),
-    (
-        "avutil",
-        "libavutil/libavutil.a",
-    ),
-    (
-        "swresample",
-        "libswresample/libswresample.a",
-    ),
-    (
-        "swscale",
-        "libswscale/libswscale.a",
-    ),
+// `Library` and its pure helpers live in `build_support.rs` (included below)
+// so `tests/build_support.rs` can cover them under a real `cargo test`
+// target - a build script's own `#[cfg(test)]` items never run.
+include!("build_support.rs");
+
+pub const LIBRARIES: &[Library] = &[
+    Library {
+        name: "avcodec",
+        static_lib: "libavcodec/libavcodec.a",
+        search_path: "libavcodec",
+        required_feature: Some("avcodec"),
+    },
+    Library {
+        name: "avdevice",
+        static_lib: "libavdevice/libavdevice.a",
+        search_path: "libavdevice",
+        required_feature: Some("avdevice"),
+    },
+    Library {
+        name: "avfilter",
+        static_lib: "libavfilter/libavfilter.a",
+        search_path: "libavfilter",
+        required_feature: Some("avfilter"),
+    },
+    Library {
+        name: "avformat",
+        static_lib: "libavformat/libavformat.a",
+        search_path: "libavformat",
+        required_feature: Some("avformat"),
+    },
+    Library {
+        name: "avutil",
+        static_lib: "libavutil/libavutil.a",
+        search_path: "libavutil",
+        required_feature: None,
+    },
+    Library {
+        name: "swresample",
+        static_lib: "libswresample/libswresample.a",
+        search_path: "libswresample",
+        required_feature: Some("swresample"),
+    },
+    Library {
+        name: "swscale",
+        static_lib: "libswscale/libswscale.a",
+        search_path: "libswscale",
+        required_feature: Some("swscale"),
+    },
 ];
 
 pub const SEARCH_PATHS: &[&str] = &[
@@ -103,6 +124,28 @@ impl bindgen::callbacks::ParseCallbacks for IgnoreMacros {
     }
 }
 
+// Picks the right integer width/signedness for FFmpeg's integer macros,
+// following the model in ffmpeg-sys-next/third's callbacks. Without this,
+// bindgen falls back to whatever width fits the macro's literal value, which
+// rarely matches how FFmpeg actually uses the constant and forces downstream
+// callers into `as i64`/`as u64` casts at every use site.
+#[derive(Debug, Clone, Default)]
+struct FfmpegIntKinds;
+
+impl bindgen::callbacks::ParseCallbacks for FfmpegIntKinds {
+    fn int_macro(&self, name: &str, value: i64) -> Option<bindgen::callbacks::IntKind> {
+        if name == "AV_NOPTS_VALUE" || name.starts_with("AV_TIME_BASE") {
+            Some(bindgen::callbacks::IntKind::I64)
+        } else if name.starts_with("AV_CH_") {
+            Some(bindgen::callbacks::IntKind::U64)
+        } else if (name.starts_with("AVERROR_") || name.starts_with("FFERRTAG")) && value < 0 {
+            Some(bindgen::callbacks::IntKind::I32)
+        } else {
+            None
+        }
+    }
+}
+
 fn command(program: &str) -> Command {
     let mut cmd = Command::new(program);
 
@@ -114,35 +157,486 @@ fn command(program: &str) -> Command {
     cmd
 }
 
+///////////////////////////////////////////////////////////////////////////////
+// SYSTEM / PRE-BUILT FFMPEG
+///////////////////////////////////////////////////////////////////////////////
+
+// If the user already has FFmpeg installed (via `FFMPEG_DIR` or discoverable
+// through pkg-config), skip the vendored `./configure && make` pipeline
+// entirely and link against that instead. This is the difference between a
+// multi-minute build and an instant one for CI and system-package users.
+struct SystemFfmpeg {
+    include_paths: Vec<PathBuf>,
+}
+
+// `FFMPEG_DIR` points at an FFmpeg install prefix, e.g. `$FFMPEG_DIR/lib` and
+// `$FFMPEG_DIR/include`. We can't tell from the directory alone whether the
+// libraries there are static or shared, so we link dynamically, matching how
+// a system package manager would normally install FFmpeg.
+fn try_ffmpeg_dir() -> Option<SystemFfmpeg> {
+    let ffmpeg_dir = PathBuf::from(env::var_os("FFMPEG_DIR")?);
+
+    println!(
+        "cargo:rustc-link-search=native={}",
+        ffmpeg_dir.join("lib").to_str().expect("PathBuf to str")
+    );
+    for lib in LIBRARIES.iter().filter(|lib| lib.is_enabled()) {
+        println!("cargo:rustc-link-lib=dylib={}", lib.name);
+    }
+
+    Some(SystemFfmpeg {
+        include_paths: vec![ffmpeg_dir.join("include")],
+    })
+}
+
+// Falls back to pkg-config. A successful result here means the vendored
+// `./configure && make` pipeline is skipped entirely, so this requires every
+// enabled library to be found - a partial install just falls through to the
+// vendored build instead of linking an incomplete set. `pkg_config::Config::
+// probe` emits its own `cargo:rustc-link-*` directives, so we only need to
+// collect include paths.
+fn try_pkg_config() -> Option<SystemFfmpeg> {
+    let probe = |lib: &Library, cargo_metadata: bool| {
+        pkg_config::Config::new()
+            .atleast_version("4")
+            .cargo_metadata(cargo_metadata)
+            .probe(&format!("lib{}", lib.name))
+            .ok()
+    };
+    // First pass emits nothing, so a partial install leaves no link directives behind.
+    if !LIBRARIES.iter().filter(|lib| lib.is_enabled()).all(|lib| probe(lib, false).is_some()) {
+        return None;
+    }
+    let mut include_paths = Vec::new();
+    for lib in LIBRARIES.iter().filter(|lib| lib.is_enabled()) {
+        include_paths.extend(probe(lib, true)?.include_paths);
+    }
+    Some(SystemFfmpeg { include_paths })
+}
+
+fn find_system_ffmpeg() -> Option<SystemFfmpeg> {
+    try_ffmpeg_dir().or_else(try_pkg_config)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// EXTERNAL CODECS
+///////////////////////////////////////////////////////////////////////////////
+
+// Describes an external encoder/decoder library wired in through its own
+// `*-sys` crate (e.g. `x264-sys`), following the `links = "..."` key in that
+// crate's Cargo.toml: Cargo exposes its build-script output as
+// `DEP_<dep_key>_LIBS`/`DEP_<dep_key>_PKGCONFIG` env vars, which we fold into
+// the FFmpeg `./configure` invocation. Adding a future codec is a one-line
+// table entry rather than a copy-pasted block.
+struct ExternalCodec {
+    // matches both `--enable-lib<name>` and the static lib name to link
+    name: &'static str,
+    cargo_feature: &'static str,
+    // the crate's `links` key, upper-cased with `-` turned into `_`
+    dep_key: &'static str,
+    extra_configure_flags: &'static [&'static str],
+}
+
+const EXTERNAL_CODECS: &[ExternalCodec] = &[
+    ExternalCodec {
+        name: "x264",
+        cargo_feature: "x264",
+        dep_key: "X264",
+        extra_configure_flags: &[],
+    },
+    ExternalCodec {
+        name: "x265",
+        cargo_feature: "x265",
+        dep_key: "X265",
+        extra_configure_flags: &["--enable-gpl"],
+    },
+    ExternalCodec {
+        name: "vpx",
+        cargo_feature: "vpx",
+        dep_key: "VPX",
+        extra_configure_flags: &[],
+    },
+    ExternalCodec {
+        name: "fdk-aac",
+        cargo_feature: "fdk-aac",
+        dep_key: "FDK_AAC",
+        extra_configure_flags: &["--enable-nonfree"],
+    },
+    ExternalCodec {
+        name: "dav1d",
+        cargo_feature: "dav1d",
+        dep_key: "DAV1D",
+        extra_configure_flags: &[],
+    },
+];
+
+// Enables `codec` in the configure invocation and emits its link directives,
+// if (and only if) the matching Cargo feature is on. Mirrors the bespoke
+// `CARGO_FEATURE_X264` branch this used to be.
+fn apply_external_codec(
+    codec: &ExternalCodec,
+    configure_flags: &mut Vec<String>,
+    pkg_config_path: &mut Option<std::ffi::OsString>,
+) {
+    if env::var_os(Library::feature_env_var(codec.cargo_feature)).is_none() {
+        return;
+    }
+
+    configure_flags.push(format!("--enable-lib{}", codec.name));
+    configure_flags.extend(codec.extra_configure_flags.iter().map(|flag| String::from(*flag)));
+
+    let dep_libs = env::var_os(format!("DEP_{}_LIBS", codec.dep_key))
+        .unwrap_or_else(|| panic!("DEP_{}_LIBS not set - is the {} sys crate a dependency?", codec.dep_key, codec.name));
+    println!("cargo:rustc-link-search=native={}", dep_libs.to_str().expect("PathBuf to str"));
+    println!("cargo:rustc-link-lib=static={}", codec.name);
+
+    let mut dep_pkg_config = env::var_os(format!("DEP_{}_PKGCONFIG", codec.dep_key))
+        .unwrap_or_else(|| panic!("DEP_{}_PKGCONFIG not set - is the {} sys crate a dependency?", codec.dep_key, codec.name));
+
+    // append existing pkg_config path - make sure this codec's pkgconfig has precedence:
+    if let Some(path) = pkg_config_path.take() {
+        dep_pkg_config.push(":");
+        dep_pkg_config.push(path);
+    }
+    *pkg_config_path = Some(dep_pkg_config);
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// SOURCE ACQUISITION
+///////////////////////////////////////////////////////////////////////////////
+
+// Pinned SHA-256 digests for every FFmpeg release tarball we know how to
+// fetch, copied from https://ffmpeg.org/releases/SHA256SUMS. This is what
+// makes `FFMPEG_VERSION` safe to use: without it, a compromised mirror (or a
+// flaky download) could silently feed `./configure` a tampered source tree.
+// Re-sync this table (and double check every digest is the full 64 hex
+// chars a SHA-256 digest actually is) whenever a new version is added.
+const FFMPEG_SOURCE_CHECKSUMS: &[(&str, &str)] = &[
+    ("6.1", "8684f4b00f94b85461884c53d08596f61266578216cce3d89b851b6e9c84fbc8"),
+    ("6.0", "828f7e9ad3608ec7186b532f98ba04e8c0c862e12efdb5c28745e6a63c042cb9"),
+    ("5.1.4", "37b00dcf6b174d428f4f6f2f33fd8700e88ecb04aecfa3dd09c10f1e15fbb9fa"),
+];
+
+fn ffmpeg_checksum_for(version: &str) -> &'static str {
+    FFMPEG_SOURCE_CHECKSUMS
+        .iter()
+        .find(|(v, _)| *v == version)
+        .map(|(_, sha256)| *sha256)
+        .unwrap_or_else(|| {
+            panic!(
+                "no pinned SHA-256 digest for FFMPEG_VERSION={:?}; add one to FFMPEG_SOURCE_CHECKSUMS",
+                version
+            )
+        })
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(bytes);
+    hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Downloads `ffmpeg-<version>.tar.gz` into memory, checks it against the
+// pinned digest table, then extracts it into `source_path` - stripping the
+// tarball's top-level `ffmpeg-<version>/` directory - following the same
+// GzDecoder+tar::Archive flow as the x264-dev build script's download path.
+// This is the alternative to `cp -r`-ing the vendored `ffmpeg-src` tree,
+// letting users target multiple FFmpeg releases without the crate shipping a
+// full source tree for each.
+fn download_ffmpeg_source(version: &str, source_path: &Path) {
+    let url = format!("https://ffmpeg.org/releases/ffmpeg-{}.tar.gz", version);
+    let expected_sha256 = ffmpeg_checksum_for(version);
+
+    let mut tarball = Vec::new();
+    ureq::get(&url)
+        .call()
+        .unwrap_or_else(|err| panic!("failed to download {}: {}", url, err))
+        .into_reader()
+        .read_to_end(&mut tarball)
+        .expect("failed to read downloaded ffmpeg tarball");
+
+    let actual_sha256 = sha256_hex(&tarball);
+    if actual_sha256 != expected_sha256 {
+        panic!(
+            "checksum mismatch for {}:\n  expected {}\n  got      {}",
+            url, expected_sha256, actual_sha256
+        );
+    }
+
+    if source_path.exists() {
+        std::fs::remove_dir_all(source_path).expect("failed to clear stale ffmpeg-src");
+    }
+    std::fs::create_dir_all(source_path).expect("failed to create ffmpeg-src dir");
+
+    let mut archive = Archive::new(GzDecoder::new(&tarball[..]));
+    for entry in archive.entries().expect("failed to read ffmpeg tarball entries") {
+        let mut entry = entry.expect("failed to read ffmpeg tarball entry");
+        let path = entry.path().expect("entry path").into_owned();
+        let top_level_dir = path.components().next().expect("tarball entry has a path");
+        let relative = path.strip_prefix(top_level_dir).expect("path has top-level dir prefix");
+        if relative.as_os_str().is_empty() {
+            continue;
+        }
+        // Refuse entries that would unpack outside `source_path` - the
+        // checksum only vouches for the tarball's bytes as a whole, not for
+        // each individual entry's path being well-behaved.
+        if relative.components().any(|component| matches!(component, std::path::Component::ParentDir)) {
+            panic!("refusing to unpack path-traversing tarball entry: {:?}", path);
+        }
+        entry
+            .unpack(source_path.join(relative))
+            .expect("failed to unpack ffmpeg tarball entry");
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// FINGERPRINTING
+///////////////////////////////////////////////////////////////////////////////
+
+// Replaces a plain "does the output file exist" check with a hash of
+// whatever inputs actually determine that output, so a changed configure
+// flag, toggled feature, switched `FFMPEG_VERSION`, or edited `headers` file
+// triggers a rebuild instead of silently reusing a stale
+// `.a`/`bindings_ffmpeg.rs`. Edits inside the vendored `ffmpeg-src` tree are
+// not part of either hash.
+fn fingerprint(parts: &[&str]) -> String {
+    sha256_hex(parts.join("\u{1}").as_bytes())
+}
+
+fn fingerprint_matches(path: &Path, current: &str) -> bool {
+    std::fs::read_to_string(path).map(|stored| stored == current).unwrap_or(false)
+}
+
+// Tells Cargo which env vars this script actually consults, so it re-runs us
+// when one of them changes instead of only on source/header edits.
+fn emit_rerun_if_env_changed() {
+    let mut vars = vec![
+        String::from("FFDEV1"),
+        String::from("FFDEV2"),
+        String::from("PKG_CONFIG_PATH"),
+        String::from("PROFILE"),
+        String::from("OPT_LEVEL"),
+        String::from("FFMPEG_VERSION"),
+        String::from("FFMPEG_DIR"),
+        String::from("TARGET"),
+        String::from("HOST"),
+        String::from("CC"),
+        String::from("AR"),
+        String::from("CROSS_COMPILE"),
+        String::from("CARGO_FEATURE_GPL"),
+    ];
+    for lib in LIBRARIES {
+        if let Some(feature) = lib.required_feature {
+            vars.push(Library::feature_env_var(feature));
+        }
+    }
+    for codec in EXTERNAL_CODECS {
+        vars.push(Library::feature_env_var(codec.cargo_feature));
+        vars.push(format!("DEP_{}_LIBS", codec.dep_key));
+        vars.push(format!("DEP_{}_PKGCONFIG", codec.dep_key));
+    }
+    for var in vars {
+        println!("cargo:rerun-if-env-changed={}", var);
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// CROSS COMPILATION
+///////////////////////////////////////////////////////////////////////////////
+
+// Translates Rust's TARGET/HOST into the FFmpeg configure flags needed to
+// cross-compile, as advertised by rust-ffmpeg-sys ("supports cross-compilation
+// automatically"). Also carries the cross CC through to the cbits `cc::Build`
+// so `defs.c`/`img_utils.c` are compiled for the target ABI, not the host's.
+struct CrossCompile {
+    configure_flags: Vec<String>,
+    cc: Option<String>,
+}
+
+fn target_os_flag(target: &str) -> &'static str {
+    if target.contains("apple-darwin") {
+        "darwin"
+    } else if target.contains("linux") {
+        "linux"
+    } else if target.contains("windows") {
+        "mingw32"
+    } else {
+        panic!("don't know the FFmpeg --target-os= value for TARGET {}", target);
+    }
+}
+
+fn target_arch_flag(target: &str) -> &str {
+    target.split('-').next().expect("TARGET triple has an arch component")
+}
+
+// e.g. `aarch64-unknown-linux-gnu` -> `aarch64-linux-gnu-`: cross toolchains
+// are conventionally named after the triple with the vendor component
+// dropped.
+//
+// mingw-w64 doesn't follow that convention - its toolchain binaries are
+// `<arch>-w64-mingw32-*` regardless of Rust's `-pc-windows-gnu` vendor/env
+// components, so a `windows` target is special-cased rather than just
+// stripping the vendor part (which would guess `x86_64-windows-gnu-`, a
+// prefix no real toolchain ships under).
+fn cross_prefix_for(target: &str) -> String {
+    if target_os_flag(target) == "mingw32" {
+        return format!("{}-w64-mingw32-", target_arch_flag(target));
+    }
+    let mut parts: Vec<&str> = target.split('-').collect();
+    if parts.len() > 2 {
+        parts.remove(1);
+    }
+    format!("{}-", parts.join("-"))
+}
+
+fn find_cross_compile() -> Option<CrossCompile> {
+    let target = env::var("TARGET").expect("TARGET env var");
+    let host = env::var("HOST").expect("HOST env var");
+    if target == host {
+        return None;
+    }
+
+    let cross_prefix = env::var("CROSS_COMPILE").unwrap_or_else(|_| cross_prefix_for(&target));
+
+    let cc = env::var("CC").ok().or_else(|| {
+        let candidate = format!("{}gcc", cross_prefix);
+        command("which")
+            .arg(&candidate)
+            .output()
+            .ok()
+            .filter(|result| result.status.success())
+            .map(|_| candidate)
+    });
+
+    if cc.is_none() {
+        panic!(
+            "TARGET ({}) != HOST ({}) but no cross toolchain was discoverable; \
+             set CC and/or CROSS_COMPILE to point at one",
+            target, host
+        );
+    }
+
+    let configure_flags = vec![
+        String::from("--enable-cross-compile"),
+        format!("--arch={}", target_arch_flag(&target)),
+        format!("--target-os={}", target_os_flag(&target)),
+        format!("--cross-prefix={}", cross_prefix),
+    ];
+
+    Some(CrossCompile { configure_flags, cc })
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 // BUILD PIPELINE
 ///////////////////////////////////////////////////////////////////////////////
 
 fn build() {
+    emit_rerun_if_env_changed();
+
+    let cross = find_cross_compile();
+
+    if let Some(system) = find_system_ffmpeg() {
+        generate_bindings(&system.include_paths);
+        compile_cbits(&system.include_paths, cross.as_ref());
+        return;
+    }
+
     let current_dir = env::current_dir().unwrap();
     let out_path = out_dir();
     let source_path = out_path.join("ffmpeg-src");
-    // SPEED UP DEV - UNLESS IN RELASE MODE
+
+    // Computed unconditionally (not just when we think we need to rebuild)
+    // so it can feed the fingerprint below; whether we actually *run*
+    // `./configure && make` is decided after that.
+    let mut configure_flags = vec![
+        String::from("--disable-programs"),
+        String::from("--disable-doc"),
+        String::from("--disable-autodetect"),
+    ];
+
+    let mut pkg_config_path = env::var_os("PKG_CONFIG_PATH");
+
+    if env::var_os("CARGO_FEATURE_GPL").is_some() {
+        configure_flags.push(String::from("--enable-gpl"));
+    }
+
+    // PER-LIBRARY FEATURE GATING
+    configure_flags.extend(LIBRARIES.iter().filter_map(Library::configure_flag));
+
+    // EXTERNAL CODECS (x264, x265, vpx, fdk-aac, dav1d, ...)
+    for codec in EXTERNAL_CODECS {
+        apply_external_codec(codec, &mut configure_flags, &mut pkg_config_path);
+    }
+
+    // TRY TO SPEED THIS UP FOR DEV BUILDS
+    if is_debug_mode() && opt_level_eq(0) {
+        configure_flags.push(String::from("--disable-optimizations"));
+        configure_flags.push(String::from("--enable-debug"));
+        configure_flags.push(String::from("--disable-stripping"));
+    }
+
+    // CROSS COMPILATION
+    if let Some(cross) = &cross {
+        configure_flags.extend(cross.configure_flags.iter().cloned());
+    }
+
+    // SPEED UP DEV - UNLESS IN RELEASE MODE OR SOMETHING RELEVANT CHANGED
     let already_built = {
-        STATIC_LIBS
+        LIBRARIES
             .iter()
-            .map(|(_, x)| source_path.join(x))
+            .filter(|lib| lib.is_enabled())
+            .map(|lib| source_path.join(lib.static_lib))
             .all(|x| x.exists())
     };
-    let mut skip_build = already_built && !is_release_mode();
+    let enabled_features: Vec<&str> = LIBRARIES
+        .iter()
+        .filter(|lib| lib.is_enabled())
+        .map(|lib| lib.name)
+        .chain(
+            EXTERNAL_CODECS
+                .iter()
+                .filter(|codec| env::var_os(Library::feature_env_var(codec.cargo_feature)).is_some())
+                .map(|codec| codec.name),
+        )
+        .collect();
+
+    let build_fingerprint_path = out_path.join("build-fingerprint.sha256");
+    let build_fingerprint = fingerprint(&[
+        &configure_flags.join(" "),
+        &enabled_features.join(","),
+        &env::var("FFMPEG_VERSION").unwrap_or_default(),
+        &env::var("PROFILE").unwrap_or_default(),
+        &env::var("OPT_LEVEL").unwrap_or_default(),
+    ]);
+    let mut skip_build = already_built
+        && !is_release_mode()
+        && fingerprint_matches(&build_fingerprint_path, &build_fingerprint);
     if has_env_var_with_value("FFDEV1", "1") {
         skip_build = false;
     }
+    // We only ever `cp -r` it in on the vendored/offline path (below), but
+    // this has to be emitted every run regardless of `skip_build` - Cargo
+    // only honors the `rerun-if-changed` set from the *most recent* build
+    // script run, so skipping it on a cached run would silently stop
+    // tracking the vendored tree from then on.
+    if env::var_os("FFMPEG_VERSION").is_none() {
+        println!("cargo:rerun-if-changed=ffmpeg-src");
+    }
     // EXTRACT
     if !source_path.exists() || !skip_build {
-        {
-            let result = command("cp")
-                .arg("-r")
-                .arg(current_dir.join("ffmpeg-src"))
-                .arg(&source_path)
-                .output()
-                .expect("copy of ffmpeg src into out dir");
-            assert!(result.status.success());
+        match env::var("FFMPEG_VERSION") {
+            // DOWNLOAD - targets a specific upstream release instead of the vendored copy
+            Ok(version) => download_ffmpeg_source(&version, &source_path),
+            // VENDORED COPY - default/offline path
+            Err(_) => {
+                let result = command("cp")
+                    .arg("-r")
+                    .arg(current_dir.join("ffmpeg-src"))
+                    .arg(&source_path)
+                    .output()
+                    .expect("copy of ffmpeg src into out dir");
+                assert!(result.status.success());
+            }
         }
         assert!(source_path.exists());
     }
@@ -150,44 +644,7 @@ fn build() {
     if skip_build == false {
         // CONFIGURE
         {
-            let mut configure_flags = vec![
-                "--disable-programs",
-                "--disable-doc",
-                "--disable-autodetect",
-            ];
-
-            let mut pkg_config_path = env::var_os("PKG_CONFIG_PATH");
-
-            if env::var_os("CARGO_FEATURE_GPL").is_some() {
-                configure_flags.push("--enable-gpl");
-            }
-
-            if env::var_os("CARGO_FEATURE_X264").is_some() {
-                configure_flags.push("--enable-libx264");
-
-                let x264_libs = env::var_os("DEP_X264_LIBS").unwrap();
-                println!("cargo:rustc-link-search=native={}", x264_libs.to_str().expect("PathBuf to str"));
-                println!("cargo:rustc-link-lib=static=x264");
-
-                let mut x264_pkg_config = env::var_os("DEP_X264_PKGCONFIG").unwrap();
-
-                // append existing pkg_config path - make sure x264's pkgconfig has precedence:
-                if let Some(path) = pkg_config_path {
-                    x264_pkg_config.push(":");
-                    x264_pkg_config.push(path);
-                }
-
-                pkg_config_path = Some(x264_pkg_config);
-            }
-
-            // TRY TO SPEED THIS UP FOR DEV BUILDS
-            if is_debug_mode() && opt_level_eq(0) {
-                configure_flags.push("--disable-optimizations");
-                configure_flags.push("--enable-debug");
-                configure_flags.push("--disable-stripping");
-            }
-
-            let eval_configure = |flags: &[&str]| {
+            let eval_configure = |flags: &[String]| {
                 let mut configure = command("bash");
                 configure.arg("./configure");
 
@@ -195,6 +652,15 @@ fn build() {
                     configure.env("PKG_CONFIG_PATH", path);
                 }
 
+                if let Some(cross) = &cross {
+                    if let Some(cc) = &cross.cc {
+                        configure.env("CC", cc);
+                    }
+                    if let Ok(ar) = env::var("AR") {
+                        configure.env("AR", ar);
+                    }
+                }
+
                 configure
                     .current_dir(&source_path)
                     .args(flags)
@@ -211,7 +677,7 @@ fn build() {
                     .any(|x| x.contains("nasm/yasm not found or too old"));
                 // MAYBE RETRY (USE CRIPPLED BUILD)
                 if nasm_yasm_issue {
-                    configure_flags.push("--disable-x86asm");
+                    configure_flags.push(String::from("--disable-x86asm"));
                     let result = eval_configure(&configure_flags);
                     if !result.status.success() {
                         let stderr = String::from_utf8(result.stderr).expect("invalid str");
@@ -246,6 +712,7 @@ fn build() {
                 panic!("make failed:\n{}", vec![stderr, stdout].join("\n\n"));
             }
         }
+        std::fs::write(&build_fingerprint_path, &build_fingerprint).expect("failed to write build fingerprint");
     }
     // LINK
     println!("cargo:rustc-link-search=native={}", source_path.to_str().expect("PathBuf to str"));
@@ -254,77 +721,121 @@ fn build() {
             source_path.join(path).to_str().expect("PathBuf as str")
         });
     }
-    for (name, _) in STATIC_LIBS {
-        println!("cargo:rustc-link-lib=static={}", name);
+    for directive in LIBRARIES.iter().filter_map(Library::link_directive) {
+        println!("{}", directive);
     }
     // CODEGEN
-    {
-        // SETUP
-        println!("rerun-if-changed=headers");
-        let ffmpeg_headers = std::fs::read("headers").expect("unable to read headers file");
-        let ffmpeg_headers = String::from_utf8(ffmpeg_headers).expect("invalid utf8 file");
-        let ffmpeg_headers = ffmpeg_headers
-            .lines()
-            .collect::<Vec<&str>>();
-        assert!(
-            ffmpeg_headers
-                .iter()
-                .map(|x| x.trim())
-                .all(|x| !x.is_empty())
-        );
+    generate_bindings(&[source_path.clone()]);
+    // COMPILE CBITS
+    compile_cbits(&[source_path.clone()], cross.as_ref());
+}
 
-        let gen_file_name = "bindings_ffmpeg.rs";
-        let ignored_macros = IgnoreMacros(HashSet::from_iter(vec![
-            String::from("FP_INFINITE"),
-            String::from("FP_NAN"),
-            String::from("FP_NORMAL"),
-            String::from("FP_SUBNORMAL"),
-            String::from("FP_ZERO"),
-            String::from("IPPORT_RESERVED"),
-        ]));
-        let mut skip_codegen = out_path.join(gen_file_name).exists();
-        if has_env_var_with_value("FFDEV2", "2") {
-            skip_codegen = false;
-        }
-        // CONFIG
-        if !skip_codegen {
-            let codegen = bindgen::Builder::default();
-            let codegen = codegen.clang_arg(format!("-I{}", source_path.to_str().expect("PathBuf to str")));
-            let mut missing = Vec::new();
-            let codegen = ffmpeg_headers
-                .iter()
-                .fold(codegen, |codegen: bindgen::Builder, path: &&str| -> bindgen::Builder {
-                    let path: &str = path.clone();
-                    let path: PathBuf = source_path.join(path);
-                    let path: &str = path.to_str().expect("PathBuf to str");
-                    if !PathBuf::from(path).exists() {
-                        missing.push(String::from(path));
+// Generates `bindings_ffmpeg.rs` by parsing the `headers` list against
+// whichever `include_paths` were found - either the vendored `ffmpeg-src`
+// tree or a system/pre-built FFmpeg's include directory.
+fn generate_bindings(include_paths: &[PathBuf]) {
+    let out_path = out_dir();
+    // SETUP
+    println!("cargo:rerun-if-changed=headers");
+    let ffmpeg_headers = std::fs::read("headers").expect("unable to read headers file");
+    let ffmpeg_headers = String::from_utf8(ffmpeg_headers).expect("invalid utf8 file");
+    let ffmpeg_headers = ffmpeg_headers
+        .lines()
+        .collect::<Vec<&str>>();
+    assert!(
+        ffmpeg_headers
+            .iter()
+            .map(|x| x.trim())
+            .all(|x| !x.is_empty())
+    );
+
+    let gen_file_name = "bindings_ffmpeg.rs";
+    let ignored_macros = IgnoreMacros(HashSet::from_iter(vec![
+        String::from("FP_INFINITE"),
+        String::from("FP_NAN"),
+        String::from("FP_NORMAL"),
+        String::from("FP_SUBNORMAL"),
+        String::from("FP_ZERO"),
+        String::from("IPPORT_RESERVED"),
+    ]));
+
+    let codegen_fingerprint_path = out_path.join("codegen-fingerprint.sha256");
+    let codegen_fingerprint = fingerprint(&[
+        &ffmpeg_headers.join("\n"),
+        &format!("{:?}", include_paths),
+        &env::var("FFMPEG_VERSION").unwrap_or_default(),
+        &env::var("PROFILE").unwrap_or_default(),
+        &env::var("OPT_LEVEL").unwrap_or_default(),
+    ]);
+    let mut skip_codegen =
+        out_path.join(gen_file_name).exists() && fingerprint_matches(&codegen_fingerprint_path, &codegen_fingerprint);
+    if has_env_var_with_value("FFDEV2", "2") {
+        skip_codegen = false;
+    }
+    // CONFIG
+    if !skip_codegen {
+        let codegen = bindgen::Builder::default();
+        let codegen = include_paths.iter().fold(codegen, |codegen, include_path| {
+            codegen.clang_arg(format!("-I{}", include_path.to_str().expect("PathBuf to str")))
+        });
+        // True enumerations get a real Rust enum; everything else (flag-like
+        // enums, plain macros) keeps bindgen's default constified treatment.
+        let codegen = codegen.rustified_enum("AVCodecID|AVPixelFormat|AVSampleFormat");
+        let mut missing = Vec::new();
+        let codegen = ffmpeg_headers
+            .iter()
+            .fold(codegen, |codegen: bindgen::Builder, header: &&str| -> bindgen::Builder {
+                let header: &str = header.clone();
+                let found = include_paths
+                    .iter()
+                    .map(|include_path| include_path.join(header))
+                    .find(|path| path.exists());
+                match found {
+                    Some(path) => codegen.header(path.to_str().expect("PathBuf to str")),
+                    None => {
+                        missing.push(String::from(header));
                         codegen
-                    } else {
-                        codegen.header(path)
                     }
-                });
-            if !missing.is_empty() {
-                panic!("missing headers: {:#?}", missing);
-            }
-            // RUN
-            codegen
-                .parse_callbacks(Box::new(ignored_macros.clone()))
-                .layout_tests(false)
-                .rustfmt_bindings(true)
-                .detect_include_paths(true)
-                .generate_comments(true)
-                .generate()
-                .expect("Unable to generate bindings")
-                .write_to_file(out_path.join(gen_file_name))
-                .expect("Couldn't write bindings!");
+                }
+            });
+        if !missing.is_empty() {
+            panic!("missing headers: {:#?}", missing);
         }
+        // RUN
+        codegen
+            .parse_callbacks(Box::new(ignored_macros.clone()))
+            .parse_callbacks(Box::new(FfmpegIntKinds::default()))
+            .layout_tests(false)
+            .rustfmt_bindings(true)
+            .detect_include_paths(true)
+            .generate_comments(true)
+            .generate()
+            .expect("Unable to generate bindings")
+            .write_to_file(out_path.join(gen_file_name))
+            .expect("Couldn't write bindings!");
+        std::fs::write(&codegen_fingerprint_path, &codegen_fingerprint).expect("failed to write codegen fingerprint");
     }
-    // COMPILE CBITS
-    cc::Build::new()
-        .include({
-            source_path.to_str().expect("PathBuf to str")
-        })
+}
+
+fn compile_cbits(include_paths: &[PathBuf], cross: Option<&CrossCompile>) {
+    // `emit_rerun_if_env_changed` and `generate_bindings`'s own
+    // `cargo:rerun-if-changed=headers` line switch Cargo off its default
+    // "rerun if anything in the package changed" scan, so from here on we're
+    // responsible for naming every input ourselves - `cc::Build` doesn't emit
+    // `rerun-if-changed` for the files it compiles.
+    println!("cargo:rerun-if-changed=cbits/defs.c");
+    println!("cargo:rerun-if-changed=cbits/img_utils.c");
+
+    let mut build = cc::Build::new();
+    for include_path in include_paths {
+        build.include(include_path.to_str().expect("PathBuf to str"));
+    }
+    if let Some(cross) = cross {
+        if let Some(cc) = &cross.cc {
+            build.compiler(cc);
+        }
+    }
+    build
         .file("cbits/defs.c")
         .file("cbits/img_utils.c")
         .compile("cbits");
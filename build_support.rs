@@ -0,0 +1,59 @@
+// Pure, build-script-independent logic lives here rather than directly in
+// `build.rs` so it can be exercised by `tests/build_support.rs` - Cargo never
+// runs a build script's own `#[cfg(test)]` items (a build script isn't a test
+// target), so anything we want real coverage for has to live in a file an
+// integration test can pull in too. Included into `build.rs` via `include!`,
+// not as a separate crate, so it stays a single source of truth without
+// needing its own `[lib]`/`[[bin]]` entry.
+
+// A `Library` describes one of FFmpeg's `lib*` components. Libraries whose
+// `required_feature` is `Some(..)` are optional: they're only configured,
+// built and linked when the matching Cargo feature is enabled, which lets
+// downstream crates build a slim FFmpeg (e.g. decode-only, no avdevice/avfilter)
+// and cut compile time substantially. `avutil` has no `required_feature`
+// because every other library depends on it, so it's always on.
+pub struct Library {
+    pub name: &'static str,
+    pub static_lib: &'static str,
+    pub search_path: &'static str,
+    pub required_feature: Option<&'static str>,
+}
+
+impl Library {
+    fn is_enabled(&self) -> bool {
+        match self.required_feature {
+            Some(feature) => std::env::var_os(Self::feature_env_var(feature)).is_some(),
+            None => true,
+        }
+    }
+
+    fn feature_env_var(feature: &str) -> String {
+        format!("CARGO_FEATURE_{}", feature.to_uppercase().replace('-', "_"))
+    }
+
+    // The `--enable-<name>`/`--disable-<name>` configure flag for this
+    // library, or `None` for an always-on library like `avutil` that has
+    // nothing to enable/disable. Pulled out as a pure function of
+    // `is_enabled()` so the enable/disable decision isn't buried inline in
+    // `build()`.
+    fn configure_flag(&self) -> Option<String> {
+        if self.required_feature.is_none() {
+            return None;
+        }
+        Some(if self.is_enabled() {
+            format!("--enable-{}", self.name)
+        } else {
+            format!("--disable-{}", self.name)
+        })
+    }
+
+    // The `cargo:rustc-link-lib=static=<name>` directive for this library,
+    // or `None` if it isn't enabled and so isn't built at all.
+    fn link_directive(&self) -> Option<String> {
+        if self.is_enabled() {
+            Some(format!("cargo:rustc-link-lib=static={}", self.name))
+        } else {
+            None
+        }
+    }
+}
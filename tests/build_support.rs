@@ -0,0 +1,54 @@
+// Exercises the pure `Library` helpers from `build_support.rs` under a real
+// `cargo test` target - unlike a `#[cfg(test)]` module inside `build.rs`
+// itself, which Cargo never runs (a build script isn't a test target).
+//
+// Each test that sets or clears a `CARGO_FEATURE_*` env var uses its own
+// made-up feature name as the key, since those vars are process-global and `cargo test` runs tests
+// in parallel by default.
+
+include!("../build_support.rs");
+
+#[test]
+fn always_on_library_has_no_configure_flag() {
+    let avutil = Library {
+        name: "avutil",
+        static_lib: "libavutil.a",
+        search_path: "libavutil",
+        required_feature: None,
+    };
+    assert!(avutil.is_enabled());
+    assert_eq!(avutil.configure_flag(), None);
+    assert_eq!(avutil.link_directive(), Some("cargo:rustc-link-lib=static=avutil".to_string()));
+}
+
+#[test]
+fn disabled_optional_library_emits_disable_flag_and_omits_link_directive() {
+    let feature = "test-disabled-lib";
+    std::env::remove_var(Library::feature_env_var(feature));
+    let avdevice = Library {
+        name: "avdevice",
+        static_lib: "libavdevice.a",
+        search_path: "libavdevice",
+        required_feature: Some(feature),
+    };
+    assert!(!avdevice.is_enabled());
+    assert_eq!(avdevice.configure_flag(), Some("--disable-avdevice".to_string()));
+    assert_eq!(avdevice.link_directive(), None);
+}
+
+#[test]
+fn enabled_optional_library_emits_configure_flag_and_link_directive() {
+    let feature = "test-enabled-lib";
+    let var = Library::feature_env_var(feature);
+    std::env::set_var(&var, "1");
+    let avfilter = Library {
+        name: "avfilter",
+        static_lib: "libavfilter.a",
+        search_path: "libavfilter",
+        required_feature: Some(feature),
+    };
+    assert!(avfilter.is_enabled());
+    assert_eq!(avfilter.configure_flag(), Some("--enable-avfilter".to_string()));
+    assert_eq!(avfilter.link_directive(), Some("cargo:rustc-link-lib=static=avfilter".to_string()));
+    std::env::remove_var(&var);
+}